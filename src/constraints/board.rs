@@ -0,0 +1,86 @@
+use super::typedefs::Position;
+
+const CELLS: usize = 81;
+
+// A dense, index-addressed board: cell `row*9+col` lives at `cells[row*9+col]`.
+// `Position` remains the public-facing coordinate type; conversion to/from a
+// flat index is a cheap multiply/divide, so this is a drop-in fast-lookup
+// replacement for the `HashMap<Position, u8>` peers used to be stored in.
+#[derive(Clone, Copy)]
+pub struct Board {
+  cells: [Option<u8>; CELLS],
+}
+
+impl Board {
+  pub fn empty() -> Board {
+    Board { cells: [None; CELLS] }
+  }
+
+  // `Position` can only be constructed in-grid, so `pos.index()` is always
+  // in range; these still check defensively rather than trusting that
+  // invariant all the way down to a raw slice index.
+  pub fn get(&self, pos: &Position) -> Option<u8> {
+    self.cells.get(pos.index()).copied().flatten()
+  }
+
+  pub fn set(&mut self, pos: &Position, value: u8) {
+    if let Some(cell) = self.cells.get_mut(pos.index()) {
+      *cell = Some(value);
+    }
+  }
+
+  pub fn unset(&mut self, pos: &Position) {
+    if let Some(cell) = self.cells.get_mut(pos.index()) {
+      *cell = None;
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.cells.iter().filter(|c| c.is_some()).count()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (Position, u8)> + '_ {
+    self.cells.iter().enumerate().filter_map(|(i, cell)| cell.map(|v| (Position::from_index(i), v)))
+  }
+}
+
+impl Default for Board {
+  fn default() -> Board {
+    Board::empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn get_set_unset_round_trip() {
+    let mut board = Board::empty();
+    let pos = Position::try_new(4, 2).unwrap();
+    assert_eq!(board.get(&pos), None);
+
+    board.set(&pos, 7);
+    assert_eq!(board.get(&pos), Some(7));
+    assert_eq!(board.len(), 1);
+
+    board.unset(&pos);
+    assert_eq!(board.get(&pos), None);
+    assert!(board.is_empty());
+  }
+
+  #[test]
+  fn iter_yields_only_assigned_cells() {
+    let mut board = Board::empty();
+    board.set(&Position::try_new(0, 0).unwrap(), 1);
+    board.set(&Position::try_new(8, 8).unwrap(), 9);
+
+    let mut values: Vec<(Position, u8)> = board.iter().collect();
+    values.sort_by_key(|(pos, _)| pos.index());
+    assert_eq!(values.iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![1, 9]);
+  }
+}
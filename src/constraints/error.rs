@@ -0,0 +1,25 @@
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+  OutOfBounds,
+  InvalidFormat(String),
+  ContradictoryClues,
+  Unsolvable,
+  MultipleSolutions,
+}
+
+impl fmt::Display for SolveError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SolveError::OutOfBounds => write!(f, "coordinate is outside the 0..9 grid"),
+      SolveError::InvalidFormat(reason) => write!(f, "invalid puzzle format: {}", reason),
+      SolveError::ContradictoryClues => write!(f, "the given clues already violate a constraint"),
+      SolveError::Unsolvable => write!(f, "no assignment satisfies every constraint"),
+      SolveError::MultipleSolutions => write!(f, "the puzzle does not have a unique solution"),
+    }
+  }
+}
+
+impl error::Error for SolveError {}
@@ -0,0 +1,51 @@
+use super::error::SolveError;
+use super::rng::Rng;
+use super::solve;
+use super::typedefs::{Position, Puzzle};
+
+// Produces a minimal puzzle for `template`'s configured constraint set
+// (standard, diagonal, killer, ...). Solves `template` with randomized
+// candidate ordering to get a full grid, then strips clues one at a time
+// in random order, keeping each removal only while the puzzle still has
+// exactly one solution.
+pub fn generate(template: &Puzzle) -> Result<Puzzle, SolveError> {
+  let mut clues = solve::solve_randomized(template)?;
+
+  let mut order: Vec<Position> = (0..81).map(Position::from_index).collect();
+  Rng::seeded().shuffle(&mut order);
+
+  for pos in order {
+    let value = match clues.get(&pos) {
+      Some(value) => value,
+      None => continue,
+    };
+
+    clues.unset(&pos);
+
+    if solve::solution_count(template, &clues, 2) != 1 {
+      clues.set(&pos, value);
+    }
+  }
+
+  Ok(template.with_clues(clues))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::typedefs::Puzzle;
+  use super::*;
+  use std::collections::HashMap;
+
+  #[test]
+  fn generated_puzzle_has_a_unique_solution() {
+    let template = Puzzle::standard(HashMap::new());
+    let generated = generate(&template).unwrap();
+    assert!(generated.unique_solution().is_ok());
+  }
+
+  #[test]
+  fn unique_solution_reports_multiple_solutions_for_sparse_clues() {
+    let puzzle = Puzzle::standard(HashMap::new());
+    assert_eq!(puzzle.unique_solution(), Err(SolveError::MultipleSolutions));
+  }
+}
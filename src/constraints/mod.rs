@@ -0,0 +1,7 @@
+pub mod typedefs;
+pub mod board;
+pub mod error;
+mod rng;
+pub mod solve;
+pub mod parse;
+pub mod generate;
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::error::SolveError;
+use super::typedefs::{Position, Puzzle};
+
+// Parses the common 81-character single-line format as well as the 9-line
+// multi-line format, using `.` or `0` for empty cells.
+impl FromStr for Puzzle {
+  type Err = SolveError;
+
+  fn from_str(s: &str) -> Result<Puzzle, SolveError> {
+    let lines: Vec<&str> = s.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).collect();
+
+    let flattened: String = if lines.len() == 9 {
+      lines.concat()
+    } else {
+      s.chars().filter(|c| !c.is_whitespace()).collect()
+    };
+
+    let cell_count = flattened.chars().count();
+    if cell_count != 81 {
+      return Err(SolveError::InvalidFormat(format!("expected 81 cells, found {}", cell_count)));
+    }
+
+    let mut clues = HashMap::new();
+    for (i, ch) in flattened.chars().enumerate() {
+      let pos = Position::try_new((i / 9) as u8, (i % 9) as u8)?;
+
+      match ch {
+        '.' | '0' => {},
+        '1'..='9' => {
+          clues.insert(pos, ch.to_digit(10).unwrap() as u8);
+        },
+        other => return Err(SolveError::InvalidFormat(format!("invalid character '{}' in puzzle string", other))),
+      }
+    }
+
+    Ok(Puzzle::standard(clues))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const GRID: &str = "\
+    530070000\n\
+    600195000\n\
+    098000060\n\
+    800060003\n\
+    400803001\n\
+    700020006\n\
+    060000280\n\
+    000419005\n\
+    000080079";
+
+  #[test]
+  fn parses_81_char_line() {
+    let line: String = GRID.lines().collect();
+    let puzzle = Puzzle::from_str(&line).unwrap();
+    assert_eq!(puzzle.clues.get(&Position::try_new(0, 0).unwrap()), Some(5));
+    assert_eq!(puzzle.clues.get(&Position::try_new(0, 2).unwrap()), None);
+  }
+
+  #[test]
+  fn parses_9_line_grid() {
+    let puzzle = Puzzle::from_str(GRID).unwrap();
+    assert_eq!(puzzle.clues.get(&Position::try_new(0, 0).unwrap()), Some(5));
+    assert_eq!(puzzle.clues.get(&Position::try_new(8, 8).unwrap()), Some(9));
+  }
+
+  #[test]
+  fn rejects_wrong_cell_count() {
+    match Puzzle::from_str("123") {
+      Err(SolveError::InvalidFormat(_)) => {},
+      other => panic!("expected InvalidFormat, got {:?}", other.map(|_| ())),
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_characters() {
+    let line: String = GRID.lines().collect::<Vec<_>>().join("").replacen('5', "x", 1);
+    match Puzzle::from_str(&line) {
+      Err(SolveError::InvalidFormat(_)) => {},
+      other => panic!("expected InvalidFormat, got {:?}", other.map(|_| ())),
+    }
+  }
+}
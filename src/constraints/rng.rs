@@ -0,0 +1,31 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Minimal xorshift64 PRNG, used to randomize search and clue-removal order.
+// Kept in-tree rather than pulling in a `rand` dependency for this one use.
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn seeded() -> Rng {
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_nanos() as u64)
+      .unwrap_or(1);
+    Rng { state: if nanos == 0 { 0x9E37_79B9_7F4A_7C15 } else { nanos } }
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state << 13;
+    self.state ^= self.state >> 7;
+    self.state ^= self.state << 17;
+    self.state
+  }
+
+  pub fn shuffle<T>(&mut self, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+      let j = (self.next_u64() as usize) % (i + 1);
+      items.swap(i, j);
+    }
+  }
+}
@@ -1,10 +1,260 @@
-use std::collections::HashMap;
+use super::board::Board;
+use super::error::SolveError;
+use super::rng::Rng;
+use super::typedefs::{Position, Puzzle, Solution};
 
-use super::typedefs::{Puzzle, Solution};
+// Bit v-1 set means value v is still a possible candidate for a cell.
+const ALL_CANDIDATES: u16 = 0b1_1111_1111;
 
-pub fn solve(puzzle: Puzzle) -> Solution {
-  Solution {
-    puzzle,
-    elements: HashMap::new(),
+fn mask_for(value: u8) -> u16 {
+  1 << (value - 1)
+}
+
+fn single_candidate(mask: u16) -> Option<u8> {
+  if mask.count_ones() == 1 {
+    Some(mask.trailing_zeros() as u8 + 1)
+  } else {
+    None
+  }
+}
+
+// The candidates still open for `mask`, in the order branches should be
+// tried. With `rng` supplied, the order is shuffled (used by the generator
+// to produce varied solved grids); without it, branches run 1..=9 in order.
+fn candidate_order(mask: u16, rng: Option<&mut Rng>) -> Vec<u8> {
+  let mut values: Vec<u8> = (1..=9u8).filter(|&v| mask & mask_for(v) != 0).collect();
+  if let Some(rng) = rng {
+    rng.shuffle(&mut values);
+  }
+  values
+}
+
+#[derive(Clone)]
+struct SolveState {
+  candidates: [u16; 81],
+  assigned: Board,
+}
+
+impl SolveState {
+  fn new() -> SolveState {
+    SolveState {
+      candidates: [ALL_CANDIDATES; 81],
+      assigned: Board::empty(),
+    }
+  }
+
+  fn is_solved(&self) -> bool {
+    self.assigned.len() == 81
+  }
+
+  // Fraction of cells assigned so far, for callers that want to report
+  // partial progress on puzzles that take a while to search.
+  fn solution_rate(&self) -> f64 {
+    self.assigned.len() as f64 / 81.0
+  }
+
+  // Assigns `value` at `pos` and propagates the resulting constraints to its
+  // peers, cascading through any cells that collapse to a naked single.
+  // Returns false as soon as a cell's candidate mask is driven to zero.
+  fn assign(&mut self, puzzle: &Puzzle, pos: &Position, value: u8) -> bool {
+    if let Some(existing) = self.assigned.get(pos) {
+      return existing == value;
+    }
+    if self.candidates[pos.index()] & mask_for(value) == 0 {
+      return false;
+    }
+
+    self.assigned.set(pos, value);
+    self.candidates[pos.index()] = mask_for(value);
+
+    let mut queue = vec![(pos.clone(), value)];
+    while let Some((cur_pos, cur_value)) = queue.pop() {
+      for constraint in puzzle.constraints.iter() {
+        for (peer, forbidden) in constraint.apply(cur_value, &cur_pos, &self.assigned) {
+          if let Some(existing) = self.assigned.get(&peer) {
+            if forbidden.contains(&existing) {
+              return false;
+            }
+            continue;
+          }
+
+          let mask = &mut self.candidates[peer.index()];
+          for v in forbidden {
+            *mask &= !mask_for(v);
+          }
+
+          let remaining = *mask;
+          if remaining == 0 {
+            return false;
+          }
+          if let Some(naked_single) = single_candidate(remaining) {
+            self.assigned.set(&peer, naked_single);
+            queue.push((peer, naked_single));
+          }
+        }
+      }
+    }
+
+    puzzle.constraints.iter().all(|c| c.is_satisfied(&self.assigned))
+  }
+
+  // The unassigned cell with the fewest remaining candidates, per the
+  // minimum-remaining-values heuristic.
+  fn most_constrained(&self) -> Option<Position> {
+    (0..81)
+      .filter(|&i| self.assigned.get(&Position::from_index(i)).is_none())
+      .min_by_key(|&i| self.candidates[i].count_ones())
+      .map(Position::from_index)
+  }
+}
+
+fn search<F: FnMut(f64)>(puzzle: &Puzzle, state: &SolveState, rng: &mut Option<Rng>, on_progress: &mut F) -> Option<SolveState> {
+  if state.is_solved() {
+    return Some(state.clone());
+  }
+
+  let pos = state.most_constrained()?;
+  let mask = state.candidates[pos.index()];
+
+  for value in candidate_order(mask, rng.as_mut()) {
+    let mut branch = state.clone();
+    if branch.assign(puzzle, &pos, value) {
+      on_progress(branch.solution_rate());
+      if let Some(solved) = search(puzzle, &branch, rng, on_progress) {
+        return Some(solved);
+      }
+    }
+  }
+
+  None
+}
+
+fn seed_from_clues(puzzle: &Puzzle, clues: &Board) -> Result<SolveState, SolveError> {
+  let mut state = SolveState::new();
+  for (pos, value) in clues.iter() {
+    if !state.assign(puzzle, &pos, value) {
+      return Err(SolveError::ContradictoryClues);
+    }
+  }
+  Ok(state)
+}
+
+pub fn solve(puzzle: Puzzle) -> Result<Solution, SolveError> {
+  solve_with_progress(puzzle, |_| {})
+}
+
+// Like `solve`, but calls `on_progress` with the fraction of cells solved
+// (0.0..=1.0) every time the search commits a new assignment, so callers
+// can report partial progress on puzzles that take a while to search.
+pub fn solve_with_progress(puzzle: Puzzle, mut on_progress: impl FnMut(f64)) -> Result<Solution, SolveError> {
+  let state = seed_from_clues(&puzzle, &puzzle.clues)?;
+
+  match search(&puzzle, &state, &mut None, &mut on_progress) {
+    Some(solved) => Ok(Solution { puzzle, elements: solved.assigned }),
+    None => Err(SolveError::Unsolvable),
+  }
+}
+
+// Solves `puzzle` with randomized candidate ordering, so repeated calls
+// produce varied solved grids for the same constraint set. Used to seed
+// the puzzle generator.
+pub(crate) fn solve_randomized(puzzle: &Puzzle) -> Result<Board, SolveError> {
+  let state = seed_from_clues(puzzle, &puzzle.clues)?;
+  let mut rng = Some(Rng::seeded());
+
+  match search(puzzle, &state, &mut rng, &mut |_| {}) {
+    Some(solved) => Ok(solved.assigned),
+    None => Err(SolveError::Unsolvable),
+  }
+}
+
+fn count_solutions(puzzle: &Puzzle, state: &SolveState, limit: usize, count: &mut usize) {
+  if *count >= limit {
+    return;
+  }
+
+  if state.is_solved() {
+    *count += 1;
+    return;
+  }
+
+  let pos = match state.most_constrained() {
+    Some(pos) => pos,
+    None => return,
+  };
+  let mask = state.candidates[pos.index()];
+
+  for value in candidate_order(mask, None) {
+    let mut branch = state.clone();
+    if branch.assign(puzzle, &pos, value) {
+      count_solutions(puzzle, &branch, limit, count);
+      if *count >= limit {
+        return;
+      }
+    }
+  }
+}
+
+// Counts solutions to `puzzle` seeded with `clues` (rather than
+// `puzzle.clues`), up to `limit`. Letting callers supply the clue set
+// separately from the puzzle lets the generator test clue removals
+// without rebuilding the constraint set each time.
+pub(crate) fn solution_count(puzzle: &Puzzle, clues: &Board, limit: usize) -> usize {
+  let state = match seed_from_clues(puzzle, clues) {
+    Ok(state) => state,
+    Err(_) => return 0,
+  };
+
+  let mut count = 0;
+  count_solutions(puzzle, &state, limit, &mut count);
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+
+  use super::super::typedefs::Puzzle;
+  use super::*;
+
+  const PUZZLE: &str = "530070000600195000098000060800060003400803001700020006060000280000419005000080079";
+  const SOLUTION: &str = "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+  #[test]
+  fn solves_known_puzzle() {
+    let puzzle = Puzzle::from_str(PUZZLE).unwrap();
+    let solved = solve(puzzle).unwrap();
+
+    let grid: String = (0..81)
+      .map(|i| solved.elements.get(&Position::from_index(i)).unwrap().to_string())
+      .collect();
+    assert_eq!(grid, SOLUTION);
+  }
+
+  #[test]
+  fn contradictory_clues_error_out() {
+    let mut clues = std::collections::HashMap::new();
+    clues.insert(Position::try_new(0, 0).unwrap(), 5);
+    clues.insert(Position::try_new(0, 1).unwrap(), 5);
+
+    let puzzle = Puzzle::standard(clues);
+    match solve(puzzle) {
+      Err(SolveError::ContradictoryClues) => {},
+      other => panic!("expected ContradictoryClues, got {:?}", other.map(|_| ())),
+    }
+  }
+
+  #[test]
+  fn solve_with_progress_reports_rate_up_to_one() {
+    // An empty grid can't be solved by propagation alone, so this forces
+    // `search` to actually branch and report progress along the way.
+    let puzzle = Puzzle::standard(std::collections::HashMap::new());
+
+    let mut rates = Vec::new();
+    solve_with_progress(puzzle, |rate| rates.push(rate)).unwrap();
+
+    assert!(!rates.is_empty());
+    assert!(rates.iter().all(|&rate| (0.0..=1.0).contains(&rate)));
+    assert_eq!(rates.last(), Some(&1.0));
   }
-}
\ No newline at end of file
+}
@@ -2,12 +2,48 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt;
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+use super::board::Board;
+use super::error::SolveError;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Position {
   row: u8,
   col: u8,
 }
 
+impl Position {
+  // The only public constructor: every `Position` that leaves this crate is
+  // guaranteed in-grid, so downstream code (flat board indexing, constraint
+  // lookups) never has to re-check bounds.
+  pub fn try_new(row: u8, col: u8) -> Result<Position, SolveError> {
+    if row < 9 && col < 9 {
+      Ok(Position { row, col })
+    } else {
+      Err(SolveError::OutOfBounds)
+    }
+  }
+
+  pub fn row(&self) -> u8 {
+    self.row
+  }
+
+  pub fn col(&self) -> u8 {
+    self.col
+  }
+
+  // Flat `row*9+col` index into a `Board`, for the hot propagation path.
+  pub fn index(&self) -> usize {
+    self.row as usize * 9 + self.col as usize
+  }
+
+  // Every call site already bounds `index` to 0..81 (Board's flat layout),
+  // so this stays crate-internal rather than reopening the unchecked
+  // construction `try_new` exists to close off.
+  pub(crate) fn from_index(index: usize) -> Position {
+    Position { row: (index / 9) as u8, col: (index % 9) as u8 }
+  }
+}
+
 impl fmt::Display for Position {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "(r: {}, c: {})", self.row, self.col)
@@ -30,20 +66,21 @@ impl CellPosition {
     }
   }
 
-  fn for_coord(coord: u8) -> CellPosition {
+  fn for_coord(coord: u8) -> Result<CellPosition, SolveError> {
     match coord {
-      0..3 => CellPosition::START,
-      3..6 => CellPosition::MIDDLE,
-      6..9 => CellPosition::END,
-      _ => panic!("Invalid coordinate!"),
+      0..3 => Ok(CellPosition::START),
+      3..6 => Ok(CellPosition::MIDDLE),
+      6..9 => Ok(CellPosition::END),
+      _ => Err(SolveError::OutOfBounds),
     }
   }
 
-  fn for_position(pos: &Position) -> (CellPosition, CellPosition) {
-    (Self::for_coord(pos.row), Self::for_coord(pos.col))
+  fn for_position(pos: &Position) -> Result<(CellPosition, CellPosition), SolveError> {
+    Ok((Self::for_coord(pos.row)?, Self::for_coord(pos.col)?))
   }
 }
 
+#[derive(Clone)]
 pub struct Group {
   positions: Vec<Position>,
 }
@@ -96,21 +133,18 @@ impl Group {
     }
   }
 
-  pub fn diag(top: bool) -> Group {
+  pub fn diag(top: bool) -> Result<Group, SolveError> {
     let offset: i8 = if top { 0 } else { 8 };
     let dir: i8 = if top { 1 } else { -1 };
     let mut positions = vec![];
 
     for col in 0..9 {
-      positions.push(Position {
-        row: (offset + dir * col).try_into().unwrap(),
-        col: col.try_into().unwrap()
-      });
+      let row: u8 = (offset + dir * col).try_into().map_err(|_| SolveError::OutOfBounds)?;
+      let col: u8 = col.try_into().map_err(|_| SolveError::OutOfBounds)?;
+      positions.push(Position::try_new(row, col)?);
     }
 
-    Group {
-      positions
-    }
+    Ok(Group { positions })
   }
 }
 
@@ -125,15 +159,35 @@ impl fmt::Display for Group {
 }
 
 pub trait Constraint {
-  fn apply(&self, value: u8, pos: &Position) -> HashMap<Position, Vec<u8>>;
+  // `assigned` is the full set of values placed so far, so constraints whose
+  // feasibility depends on more than the one value just placed (e.g. a
+  // killer-sudoku cage's running sum) can reason about the partial solution,
+  // not just `value`/`pos` in isolation.
+  fn apply(&self, value: u8, pos: &Position, assigned: &Board) -> HashMap<Position, Vec<u8>>;
+
+  fn clone_box(&self) -> Box<dyn Constraint>;
+
+  // Whether `self` still holds given everything assigned so far. `apply`
+  // only prunes candidates for cells not yet placed, so constraints that can
+  // still be violated once all of their cells are filled in (a cage whose
+  // digits don't add up) need this to catch that after the fact.
+  fn is_satisfied(&self, _assigned: &Board) -> bool {
+    true
+  }
+}
+
+impl Clone for Box<dyn Constraint> {
+  fn clone(&self) -> Box<dyn Constraint> {
+    self.clone_box()
+  }
 }
 
 pub trait GroupConstraint {
   fn get_group(pos: &Position) -> Group;
 }
 
-impl <T: GroupConstraint> Constraint for T {
-  fn apply(&self, value: u8, pos: &Position) -> HashMap<Position, Vec<u8>> {
+impl <T: GroupConstraint + Clone + 'static> Constraint for T {
+  fn apply(&self, value: u8, pos: &Position, _assigned: &Board) -> HashMap<Position, Vec<u8>> {
     let group = Self::get_group(pos);
     let mut map = HashMap::new();
     for group_pos in group.positions.iter() {
@@ -143,8 +197,13 @@ impl <T: GroupConstraint> Constraint for T {
     }
     map
   }
+
+  fn clone_box(&self) -> Box<dyn Constraint> {
+    Box::new(self.clone())
+  }
 }
 
+#[derive(Clone)]
 struct RowConstraint {}
 impl GroupConstraint for RowConstraint {
   fn get_group(pos: &Position) -> Group {
@@ -152,6 +211,7 @@ impl GroupConstraint for RowConstraint {
   }
 }
 
+#[derive(Clone)]
 struct ColumnConstraint {}
 impl GroupConstraint for ColumnConstraint {
   fn get_group(pos: &Position) -> Group {
@@ -159,35 +219,203 @@ impl GroupConstraint for ColumnConstraint {
   }
 }
 
+#[derive(Clone)]
 struct CellConstraint {}
 impl GroupConstraint for CellConstraint {
   fn get_group(pos: &Position) -> Group {
-    let (row_pos, cell_pos) = CellPosition::for_position(pos);
+    // `pos` always comes from an in-grid `Position`, so this can't fail.
+    let (row_pos, cell_pos) = CellPosition::for_position(pos)
+      .expect("position is within the 0..9 grid");
     Group::cell(row_pos, cell_pos)
   }
 }
 
+#[derive(Clone)]
 struct DiagonalConstraint {}
 impl GroupConstraint for DiagonalConstraint {
   fn get_group(pos: &Position) -> Group {
     let mut positions = vec![];
 
     if pos.row == pos.col {
-      positions.append(&mut Group::diag(true).positions);
+      positions.append(&mut Group::diag(true).expect("diagonal is within the 0..9 grid").positions);
     }
 
     if pos.row == 8 - pos.col {
-      positions.append(&mut Group::diag(false).positions);
+      positions.append(&mut Group::diag(false).expect("diagonal is within the 0..9 grid").positions);
     }
 
     Group { positions }
   }
 }
 
-struct PuzzleBuilder {
+// Positions offset from `pos` by each `(row, col)` delta, clamped to the
+// 0..9 grid (deltas that land off the board are simply dropped).
+fn offset_positions(pos: &Position, deltas: &[(i8, i8)]) -> Vec<Position> {
+  let mut positions = vec![];
+  for (dr, dc) in deltas {
+    let row = pos.row as i8 + dr;
+    let col = pos.col as i8 + dc;
+    if (0..9).contains(&row) && (0..9).contains(&col) {
+      positions.push(Position { row: row as u8, col: col as u8 });
+    }
+  }
+  positions
+}
+
+#[derive(Clone)]
+struct KnightConstraint {}
+impl GroupConstraint for KnightConstraint {
+  fn get_group(pos: &Position) -> Group {
+    const DELTAS: [(i8, i8); 8] = [
+      (-2, -1), (-2, 1), (-1, -2), (-1, 2),
+      (1, -2), (1, 2), (2, -1), (2, 1),
+    ];
+    Group { positions: offset_positions(pos, &DELTAS) }
+  }
+}
+
+#[derive(Clone)]
+struct KingConstraint {}
+impl GroupConstraint for KingConstraint {
+  fn get_group(pos: &Position) -> Group {
+    const DELTAS: [(i8, i8); 8] = [
+      (-1, -1), (-1, 0), (-1, 1),
+      (0, -1), (0, 1),
+      (1, -1), (1, 0), (1, 1),
+    ];
+    Group { positions: offset_positions(pos, &DELTAS) }
+  }
+}
+
+// Orthogonally adjacent cells may not hold consecutive values. This forbids
+// `value - 1`/`value + 1` in neighbors rather than `value` itself, so it
+// can't be expressed as a `GroupConstraint` and implements `Constraint`
+// directly instead.
+#[derive(Clone)]
+struct NonConsecutiveConstraint {}
+impl Constraint for NonConsecutiveConstraint {
+  fn apply(&self, value: u8, pos: &Position, _assigned: &Board) -> HashMap<Position, Vec<u8>> {
+    const DELTAS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+    let mut forbidden = vec![];
+    if value > 1 {
+      forbidden.push(value - 1);
+    }
+    if value < 9 {
+      forbidden.push(value + 1);
+    }
+
+    let mut map = HashMap::new();
+    if !forbidden.is_empty() {
+      for peer in offset_positions(pos, &DELTAS) {
+        map.insert(peer, forbidden.clone());
+      }
+    }
+    map
+  }
+
+  fn clone_box(&self) -> Box<dyn Constraint> {
+    Box::new(self.clone())
+  }
+}
+
+// A killer-sudoku cage: `positions` must hold distinct values summing to
+// `sum`. This can't be a `GroupConstraint`, since feasibility depends on the
+// values already placed in the cage, not just the one value being applied.
+#[derive(Clone)]
+pub struct CageConstraint {
+  positions: Vec<Position>,
+  sum: u32,
+}
+
+impl CageConstraint {
+  pub fn new(positions: Vec<Position>, sum: u32) -> CageConstraint {
+    CageConstraint { positions, sum }
+  }
+
+  // The smallest and largest sum `slots` distinct digits (drawn from 1..=9,
+  // excluding `excluded`) can possibly add up to.
+  fn completion_bounds(excluded: &[u8], slots: usize) -> (i32, i32) {
+    let available: Vec<i32> = (1..=9u8)
+      .filter(|d| !excluded.contains(d))
+      .map(|d| d as i32)
+      .collect();
+
+    let min: i32 = available.iter().take(slots).sum();
+    let max: i32 = available.iter().rev().take(slots).sum();
+    (min, max)
+  }
+}
+
+impl Constraint for CageConstraint {
+  fn apply(&self, value: u8, pos: &Position, assigned: &Board) -> HashMap<Position, Vec<u8>> {
+    if !self.positions.iter().any(|p| p == pos) {
+      return HashMap::new();
+    }
+
+    let remaining: Vec<&Position> = self.positions.iter()
+      .filter(|p| *p != pos && assigned.get(p).is_none())
+      .collect();
+
+    if remaining.is_empty() {
+      return HashMap::new();
+    }
+
+    let mut placed: Vec<u8> = self.positions.iter()
+      .filter(|p| *p != pos)
+      .filter_map(|p| assigned.get(p))
+      .collect();
+    placed.push(value);
+
+    let placed_sum: i32 = placed.iter().map(|&v| v as i32).sum();
+    let remaining_sum = self.sum as i32 - placed_sum;
+    let remaining_slots = remaining.len();
+
+    // `value` can't reappear elsewhere in the cage.
+    let mut forbidden = vec![value];
+
+    for digit in 1..=9u8 {
+      if placed.contains(&digit) {
+        continue;
+      }
+
+      let mut excluded = placed.clone();
+      excluded.push(digit);
+      let (min_rest, max_rest) = Self::completion_bounds(&excluded, remaining_slots - 1);
+      let needed = remaining_sum - digit as i32;
+
+      if needed < min_rest || needed > max_rest {
+        forbidden.push(digit);
+      }
+    }
+
+    let mut map = HashMap::new();
+    for p in remaining {
+      map.insert(Position::clone(p), forbidden.clone());
+    }
+    map
+  }
+
+  fn clone_box(&self) -> Box<dyn Constraint> {
+    Box::new(self.clone())
+  }
+
+  // `apply` only prunes candidates for the cage's still-empty cells, so a
+  // cage that's already full (e.g. a single-cell cage, or the last cell of
+  // a larger one) would otherwise never have its sum checked at all.
+  fn is_satisfied(&self, assigned: &Board) -> bool {
+    let placed: Vec<u8> = self.positions.iter().filter_map(|p| assigned.get(p)).collect();
+    if placed.len() < self.positions.len() {
+      return true;
+    }
+    placed.iter().map(|&v| v as i32).sum::<i32>() == self.sum as i32
+  }
+}
+
+pub struct PuzzleBuilder {
   groups: Vec<Group>,
   constraints: Vec<Box<dyn Constraint>>,
-  clues: HashMap<Position, u8>,
+  clues: Board,
 }
 
 impl PuzzleBuilder {
@@ -195,7 +423,7 @@ impl PuzzleBuilder {
     PuzzleBuilder {
       groups: vec![],
       constraints: vec![],
-      clues: HashMap::new(),
+      clues: Board::empty(),
     }
   }
 
@@ -208,14 +436,14 @@ impl PuzzleBuilder {
     for row in 0..9 {
       self.add_group(Group::row(row));
     }
-    self
+    self.add_constraint(Box::new(RowConstraint {}))
   }
 
   pub fn add_col_groups(&mut self) -> &mut Self {
     for col in 0..9 {
       self.add_group(Group::col(col));
     }
-    self
+    self.add_constraint(Box::new(ColumnConstraint {}))
   }
 
   pub fn add_cell_groups(&mut self) -> &mut Self {
@@ -229,13 +457,13 @@ impl PuzzleBuilder {
         self.add_group(Group::cell(*row_pos, *col_pos));
       }
     }
-    self
+    self.add_constraint(Box::new(CellConstraint {}))
   }
 
-  pub fn add_diag_groups(&mut self) -> &mut Self {
-    self.add_group(Group::diag(true));
-    self.add_group(Group::diag(false));
-    self
+  pub fn add_diag_groups(&mut self) -> Result<&mut Self, SolveError> {
+    self.add_group(Group::diag(true)?);
+    self.add_group(Group::diag(false)?);
+    Ok(self.add_constraint(Box::new(DiagonalConstraint {})))
   }
 
   pub fn add_constraint(&mut self, c: Box<dyn Constraint>) -> &mut Self {
@@ -243,13 +471,31 @@ impl PuzzleBuilder {
     self
   }
 
+  pub fn add_cage(&mut self, positions: Vec<Position>, sum: u32) -> &mut Self {
+    self.add_constraint(Box::new(CageConstraint::new(positions, sum)))
+  }
+
+  pub fn add_knight_constraint(&mut self) -> &mut Self {
+    self.add_constraint(Box::new(KnightConstraint {}))
+  }
+
+  pub fn add_king_constraint(&mut self) -> &mut Self {
+    self.add_constraint(Box::new(KingConstraint {}))
+  }
+
+  pub fn add_non_consecutive_constraint(&mut self) -> &mut Self {
+    self.add_constraint(Box::new(NonConsecutiveConstraint {}))
+  }
+
   pub fn add_clue(&mut self, pos: Position, value: u8) -> &mut Self {
-    self.clues.insert(pos, value);
+    self.clues.set(&pos, value);
     self
   }
 
   pub fn add_clues(&mut self, clues: HashMap<Position, u8>) -> &mut Self {
-    self.clues.extend(clues);
+    for (pos, value) in clues {
+      self.clues.set(&pos, value);
+    }
     self
   }
 
@@ -262,10 +508,11 @@ impl PuzzleBuilder {
   }
 }
 
+#[derive(Clone)]
 pub struct Puzzle {
-  groups: Vec<Group>,
-  constraints: Vec<Box<dyn Constraint>>,
-  clues: HashMap<Position, u8>,
+  pub(crate) groups: Vec<Group>,
+  pub(crate) constraints: Vec<Box<dyn Constraint>>,
+  pub(crate) clues: Board,
 }
 
 impl Puzzle {
@@ -278,9 +525,175 @@ impl Puzzle {
       .add_clues(clues);
     builder.build()
   }
+
+  // Counts solutions up to 2 (short-circuiting as soon as a second is
+  // found), so callers can check a puzzle has a unique answer without
+  // paying for a full enumeration.
+  pub fn solution_count(&self) -> usize {
+    super::solve::solution_count(self, &self.clues, 2)
+  }
+
+  // Ok iff `self` has exactly one solution; distinguishes an unsolvable
+  // puzzle from one whose clues are too sparse to pin down a unique grid.
+  pub fn unique_solution(&self) -> Result<(), SolveError> {
+    match self.solution_count() {
+      0 => Err(SolveError::Unsolvable),
+      1 => Ok(()),
+      _ => Err(SolveError::MultipleSolutions),
+    }
+  }
+
+  // Same groups/constraints as `self`, but with a different clue set. Used
+  // by the generator to return its stripped-down clue set under the
+  // template's constraint set.
+  pub(crate) fn with_clues(&self, clues: Board) -> Puzzle {
+    Puzzle {
+      groups: self.groups.clone(),
+      constraints: self.constraints.clone(),
+      clues,
+    }
+  }
 }
 
 pub struct Solution {
   pub puzzle: Puzzle,
-  pub elements: HashMap<Position, u8>,
+  pub elements: Board,
+}
+
+impl fmt::Display for Solution {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for row in 0..9 {
+      if row > 0 && row % 3 == 0 {
+        writeln!(f, "------+-------+------")?;
+      }
+
+      for col in 0..9 {
+        if col > 0 && col % 3 == 0 {
+          write!(f, "| ")?;
+        }
+
+        match self.elements.get(&Position { row, col }) {
+          Some(value) => write!(f, "{} ", value)?,
+          None => write!(f, ". ")?,
+        }
+      }
+
+      writeln!(f)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn pos(row: u8, col: u8) -> Position {
+    Position::try_new(row, col).unwrap()
+  }
+
+  #[test]
+  fn solution_display_renders_digits_dots_and_box_separators() {
+    let mut elements = Board::empty();
+    for i in 0..9u8 {
+      elements.set(&pos(i, i), i + 1);
+    }
+
+    let solution = Solution { puzzle: Puzzle::standard(HashMap::new()), elements };
+
+    let expected = [
+      "1 . . | . . . | . . . ",
+      ". 2 . | . . . | . . . ",
+      ". . 3 | . . . | . . . ",
+      "------+-------+------",
+      ". . . | 4 . . | . . . ",
+      ". . . | . 5 . | . . . ",
+      ". . . | . . 6 | . . . ",
+      "------+-------+------",
+      ". . . | . . . | 7 . . ",
+      ". . . | . . . | . 8 . ",
+      ". . . | . . . | . . 9 ",
+      "",
+    ]
+    .join("\n");
+
+    assert_eq!(solution.to_string(), expected);
+  }
+
+  #[test]
+  fn cage_forbids_sums_that_cant_complete() {
+    // A 2-cell cage summing to 3 can only be {1, 2}; placing a 5 should rule
+    // out every other digit in the remaining cell, since nothing pairs with
+    // a 5 to reach 3.
+    let cage = CageConstraint::new(vec![pos(0, 0), pos(0, 1)], 3);
+    let forbidden = cage.apply(5, &pos(0, 0), &Board::empty());
+    let forbidden_at_peer = forbidden.get(&pos(0, 1)).unwrap();
+    assert!((1..=9).all(|v| forbidden_at_peer.contains(&v)));
+  }
+
+  #[test]
+  fn cage_rejects_wrong_sum_on_completion() {
+    let mut assigned = Board::empty();
+    assigned.set(&pos(0, 0), 3);
+    let cage = CageConstraint::new(vec![pos(0, 0)], 7);
+    assert!(!cage.is_satisfied(&assigned));
+  }
+
+  #[test]
+  fn cage_accepts_correct_sum_on_completion() {
+    let mut assigned = Board::empty();
+    assigned.set(&pos(0, 0), 7);
+    let cage = CageConstraint::new(vec![pos(0, 0)], 7);
+    assert!(cage.is_satisfied(&assigned));
+  }
+
+  #[test]
+  fn position_try_new_rejects_out_of_range_coordinates() {
+    assert!(Position::try_new(8, 8).is_ok());
+    assert_eq!(Position::try_new(9, 0), Err(SolveError::OutOfBounds));
+    assert_eq!(Position::try_new(0, 9), Err(SolveError::OutOfBounds));
+  }
+
+  #[test]
+  fn knight_constraint_forbids_knight_move_duplicate() {
+    let forbidden = KnightConstraint {}.apply(4, &pos(3, 3), &Board::empty());
+    assert_eq!(forbidden.get(&pos(1, 2)), Some(&vec![4]));
+    assert_eq!(forbidden.get(&pos(4, 4)), None);
+  }
+
+  #[test]
+  fn king_constraint_forbids_adjacent_duplicate() {
+    let forbidden = KingConstraint {}.apply(4, &pos(3, 3), &Board::empty());
+    assert_eq!(forbidden.get(&pos(2, 2)), Some(&vec![4]));
+    assert_eq!(forbidden.get(&pos(5, 5)), None);
+  }
+
+  #[test]
+  fn non_consecutive_constraint_forbids_adjacent_consecutive() {
+    let forbidden = NonConsecutiveConstraint {}.apply(4, &pos(3, 3), &Board::empty());
+    assert_eq!(forbidden.get(&pos(3, 4)), Some(&vec![3, 5]));
+    assert_eq!(forbidden.get(&pos(3, 5)), None);
+  }
+
+  #[test]
+  fn diagonal_constraint_forbids_duplicate_on_main_diagonal_only() {
+    let forbidden = DiagonalConstraint {}.apply(4, &pos(3, 3), &Board::empty());
+    assert_eq!(forbidden.get(&pos(5, 5)), Some(&vec![4]));
+    assert_eq!(forbidden.get(&pos(3, 5)), None);
+  }
+
+  #[test]
+  fn single_cell_cage_with_wrong_clue_is_contradictory() {
+    let mut builder = PuzzleBuilder::new();
+    builder.add_row_groups().add_col_groups().add_cell_groups();
+    builder.add_cage(vec![pos(0, 0)], 7);
+    builder.add_clue(pos(0, 0), 3);
+    let puzzle = builder.build();
+
+    match super::super::solve::solve(puzzle) {
+      Err(SolveError::ContradictoryClues) => {},
+      other => panic!("expected ContradictoryClues, got {:?}", other.map(|_| ())),
+    }
+  }
 }
\ No newline at end of file